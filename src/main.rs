@@ -1,6 +1,9 @@
 use std::cmp::Ordering;
 use std::convert::From;
 use std::fmt;
+use std::fs;
+use std::io::{self, BufRead};
+use std::iter::FusedIterator;
 use std::net::{AddrParseError, Ipv4Addr, Ipv6Addr};
 use std::str::FromStr;
 use std::u128;
@@ -64,6 +67,144 @@ enum RangeParseError {
     CidrInvalid,
 }
 
+/// Saturating addition for IP address types, clamping at the all-ones
+/// address instead of wrapping or panicking on overflow.
+trait IpAdd<Rhs = Self> {
+    type Output;
+    fn saturating_add(self, rhs: Rhs) -> Self::Output;
+}
+
+/// Saturating subtraction for IP address types, clamping at the
+/// zero address instead of wrapping or panicking on underflow.
+trait IpSub<Rhs = Self> {
+    type Output;
+    fn saturating_sub(self, rhs: Rhs) -> Self::Output;
+}
+
+/// Bitwise AND between an IP address and a mask.
+trait IpBitAnd<Rhs = Self> {
+    type Output;
+    fn bitand(self, rhs: Rhs) -> Self::Output;
+}
+
+/// Bitwise OR between an IP address and a mask.
+trait IpBitOr<Rhs = Self> {
+    type Output;
+    fn bitor(self, rhs: Rhs) -> Self::Output;
+}
+
+impl IpAdd<u32> for u32 {
+    type Output = u32;
+    fn saturating_add(self, rhs: u32) -> u32 {
+        u32::saturating_add(self, rhs)
+    }
+}
+
+impl IpSub<u32> for u32 {
+    type Output = u32;
+    fn saturating_sub(self, rhs: u32) -> u32 {
+        u32::saturating_sub(self, rhs)
+    }
+}
+
+impl IpBitAnd<u32> for u32 {
+    type Output = u32;
+    fn bitand(self, rhs: u32) -> u32 {
+        self & rhs
+    }
+}
+
+impl IpBitOr<u32> for u32 {
+    type Output = u32;
+    fn bitor(self, rhs: u32) -> u32 {
+        self | rhs
+    }
+}
+
+impl IpAdd<u32> for Ipv4Addr {
+    type Output = Ipv4Addr;
+    fn saturating_add(self, rhs: u32) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self).saturating_add(rhs))
+    }
+}
+
+impl IpSub<u32> for Ipv4Addr {
+    type Output = Ipv4Addr;
+    fn saturating_sub(self, rhs: u32) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self).saturating_sub(rhs))
+    }
+}
+
+impl IpBitAnd<u32> for Ipv4Addr {
+    type Output = Ipv4Addr;
+    fn bitand(self, rhs: u32) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self) & rhs)
+    }
+}
+
+impl IpBitOr<u32> for Ipv4Addr {
+    type Output = Ipv4Addr;
+    fn bitor(self, rhs: u32) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self) | rhs)
+    }
+}
+
+impl IpAdd<u128> for u128 {
+    type Output = u128;
+    fn saturating_add(self, rhs: u128) -> u128 {
+        u128::saturating_add(self, rhs)
+    }
+}
+
+impl IpSub<u128> for u128 {
+    type Output = u128;
+    fn saturating_sub(self, rhs: u128) -> u128 {
+        u128::saturating_sub(self, rhs)
+    }
+}
+
+impl IpBitAnd<u128> for u128 {
+    type Output = u128;
+    fn bitand(self, rhs: u128) -> u128 {
+        self & rhs
+    }
+}
+
+impl IpBitOr<u128> for u128 {
+    type Output = u128;
+    fn bitor(self, rhs: u128) -> u128 {
+        self | rhs
+    }
+}
+
+impl IpAdd<u128> for Ipv6Addr {
+    type Output = Ipv6Addr;
+    fn saturating_add(self, rhs: u128) -> Ipv6Addr {
+        Ipv6Addr::from(u128::from(self).saturating_add(rhs))
+    }
+}
+
+impl IpSub<u128> for Ipv6Addr {
+    type Output = Ipv6Addr;
+    fn saturating_sub(self, rhs: u128) -> Ipv6Addr {
+        Ipv6Addr::from(u128::from(self).saturating_sub(rhs))
+    }
+}
+
+impl IpBitAnd<u128> for Ipv6Addr {
+    type Output = Ipv6Addr;
+    fn bitand(self, rhs: u128) -> Ipv6Addr {
+        Ipv6Addr::from(u128::from(self) & rhs)
+    }
+}
+
+impl IpBitOr<u128> for Ipv6Addr {
+    type Output = Ipv6Addr;
+    fn bitor(self, rhs: u128) -> Ipv6Addr {
+        Ipv6Addr::from(u128::from(self) | rhs)
+    }
+}
+
 trait IpRange: Sized {
     fn normalize(&mut self) -> &mut Self;
     fn _set_cidr(&mut self, c: u8) -> &mut Self;
@@ -75,12 +216,7 @@ trait IpRange: Sized {
 
 impl IpRange for Ipv4Range {
     fn normalize(&mut self) -> &mut Self {
-        match self.cidr {
-            0 => self.ip = 0,
-            1..=31 => self.ip &= <u32>::max_value() << (32 - self.cidr),
-            32 => {}
-            _ => panic!("invalid CIDR size {}", self.cidr),
-        };
+        self.ip = self.ip.bitand(Self::_netmask(self.cidr));
         self
     }
     fn _set_cidr(&mut self, c: u8) -> &mut Self {
@@ -123,12 +259,7 @@ impl IpRange for Ipv4Range {
 
 impl IpRange for Ipv6Range {
     fn normalize(&mut self) -> &mut Self {
-        match self.cidr {
-            0 => self.ip = 0,
-            1..=127 => self.ip &= <u128>::max_value() << (128 - self.cidr),
-            128 => {}
-            _ => panic!("invalid CIDR size {}", self.cidr),
-        };
+        self.ip = self.ip.bitand(Self::_netmask(self.cidr));
         self
     }
     fn _set_cidr(&mut self, c: u8) -> &mut Self {
@@ -219,7 +350,7 @@ impl FromStr for Ipv6Range {
             (2, Err(e)) => Err(RangeParseError::IpInvalid(e)),
             (1, Ok(i)) => Ok(Ipv6Range {
                 ip: i.into(),
-                cidr: 32,
+                cidr: 128,
             }),
             (2, Ok(i)) => match slashes[1].parse() {
                 Ok(n) => match n {
@@ -240,6 +371,486 @@ impl FromStr for Ipv6Range {
     }
 }
 
+impl Ipv4Range {
+    fn _netmask(cidr: u8) -> u32 {
+        match cidr {
+            0 => 0,
+            1..=31 => u32::max_value() << (32 - cidr),
+            32 => u32::max_value(),
+            _ => panic!("invalid CIDR size {}", cidr),
+        }
+    }
+
+    /// The network (first) address of the range.
+    fn network(&self) -> Ipv4Addr {
+        Ipv4Addr::from(self.ip.bitand(Self::_netmask(self.cidr)))
+    }
+
+    /// The broadcast (last) address of the range.
+    fn broadcast(&self) -> Ipv4Addr {
+        Ipv4Addr::from(self.ip.bitor(!Self::_netmask(self.cidr)))
+    }
+
+    /// The subnet mask of the range, e.g. `255.255.255.0` for a `/24`.
+    fn netmask(&self) -> Ipv4Addr {
+        Ipv4Addr::from(Self::_netmask(self.cidr))
+    }
+
+    /// The inverse of `netmask()`, e.g. `0.0.0.255` for a `/24`.
+    fn hostmask(&self) -> Ipv4Addr {
+        Ipv4Addr::from(!Self::_netmask(self.cidr))
+    }
+
+    /// Number of addresses covered by the range. Unlike the IPv6 counterpart
+    /// this always fits in the return type, since 2^32 fits in a `u64`.
+    fn host_count(&self) -> u64 {
+        1u64 << (32 - self.cidr)
+    }
+
+    /// Iterate over every address in the range, from the network address
+    /// through the broadcast address, inclusive.
+    fn hosts(&self) -> Ipv4HostsIter {
+        let mask = Self::_netmask(self.cidr);
+        Ipv4HostsIter {
+            next: self.ip.bitand(mask),
+            last: self.ip.bitor(!mask),
+            done: false,
+        }
+    }
+
+    /// Iterate over the child ranges of prefix length `new_prefix`. Empty if
+    /// `new_prefix` is less specific than `self.cidr`.
+    fn subnets(&self, new_prefix: u8) -> Ipv4SubnetsIter {
+        if new_prefix > 32 {
+            panic!("invalid CIDR size {}", new_prefix);
+        }
+        if new_prefix < self.cidr {
+            return Ipv4SubnetsIter {
+                next: 0,
+                last: 0,
+                step: 0,
+                cidr: new_prefix,
+                done: true,
+            };
+        }
+        let broadcast = self.ip.bitor(!Self::_netmask(self.cidr));
+        let step = if new_prefix == 0 {
+            0
+        } else {
+            1u32 << (32 - new_prefix)
+        };
+        Ipv4SubnetsIter {
+            next: self.ip,
+            last: broadcast.bitand(Self::_netmask(new_prefix)),
+            step,
+            cidr: new_prefix,
+            done: false,
+        }
+    }
+}
+
+impl Ipv6Range {
+    fn _netmask(cidr: u8) -> u128 {
+        match cidr {
+            0 => 0,
+            1..=127 => u128::max_value() << (128 - cidr),
+            128 => u128::max_value(),
+            _ => panic!("invalid CIDR size {}", cidr),
+        }
+    }
+
+    /// The network (first) address of the range.
+    fn network(&self) -> Ipv6Addr {
+        Ipv6Addr::from(self.ip.bitand(Self::_netmask(self.cidr)))
+    }
+
+    /// The last address of the range.
+    fn last_address(&self) -> Ipv6Addr {
+        Ipv6Addr::from(self.ip.bitor(!Self::_netmask(self.cidr)))
+    }
+
+    /// The subnet mask of the range, e.g. `ffff:ffff:ffff:ffff::` for a `/64`.
+    fn netmask(&self) -> Ipv6Addr {
+        Ipv6Addr::from(Self::_netmask(self.cidr))
+    }
+
+    /// The inverse of `netmask()`, e.g. `::ffff:ffff:ffff:ffff` for a `/64`.
+    fn hostmask(&self) -> Ipv6Addr {
+        Ipv6Addr::from(!Self::_netmask(self.cidr))
+    }
+
+    /// Number of addresses covered by the range, saturated to `u128::MAX`
+    /// for a `/0` (whose true count, 2^128, does not fit in a `u128`, and
+    /// whose mask shift of 128 bits would overflow besides).
+    fn host_count(&self) -> u128 {
+        if self.cidr == 0 {
+            u128::max_value()
+        } else {
+            1u128 << (128 - self.cidr)
+        }
+    }
+
+    /// Iterate over every address in the range, from the network address
+    /// through the last address, inclusive.
+    fn hosts(&self) -> Ipv6HostsIter {
+        let mask = Self::_netmask(self.cidr);
+        Ipv6HostsIter {
+            next: self.ip.bitand(mask),
+            last: self.ip.bitor(!mask),
+            done: false,
+        }
+    }
+
+    /// Iterate over the child ranges of prefix length `new_prefix`. Empty if
+    /// `new_prefix` is less specific than `self.cidr`.
+    fn subnets(&self, new_prefix: u8) -> Ipv6SubnetsIter {
+        if new_prefix > 128 {
+            panic!("invalid CIDR size {}", new_prefix);
+        }
+        if new_prefix < self.cidr {
+            return Ipv6SubnetsIter {
+                next: 0,
+                last: 0,
+                step: 0,
+                cidr: new_prefix,
+                done: true,
+            };
+        }
+        let broadcast = self.ip.bitor(!Self::_netmask(self.cidr));
+        let step = if new_prefix == 0 {
+            0
+        } else {
+            1u128 << (128 - new_prefix)
+        };
+        Ipv6SubnetsIter {
+            next: self.ip,
+            last: broadcast.bitand(Self::_netmask(new_prefix)),
+            step,
+            cidr: new_prefix,
+            done: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Ipv4HostsIter {
+    next: u32,
+    last: u32,
+    // a /0 range's last address is u32::MAX, so "next > last" cannot be used
+    // to detect exhaustion; track it explicitly instead
+    done: bool,
+}
+
+impl Iterator for Ipv4HostsIter {
+    type Item = Ipv4Addr;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let val = self.next;
+        if val == self.last {
+            self.done = true;
+        } else {
+            self.next = val + 1;
+        }
+        Some(Ipv4Addr::from(val))
+    }
+}
+
+impl DoubleEndedIterator for Ipv4HostsIter {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let val = self.last;
+        if val == self.next {
+            self.done = true;
+        } else {
+            self.last = val - 1;
+        }
+        Some(Ipv4Addr::from(val))
+    }
+}
+
+impl FusedIterator for Ipv4HostsIter {}
+
+#[derive(Debug, Clone)]
+struct Ipv6HostsIter {
+    next: u128,
+    last: u128,
+    // a /0 range's last address is u128::MAX, so "next > last" cannot be
+    // used to detect exhaustion; track it explicitly instead
+    done: bool,
+}
+
+impl Iterator for Ipv6HostsIter {
+    type Item = Ipv6Addr;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let val = self.next;
+        if val == self.last {
+            self.done = true;
+        } else {
+            self.next = val + 1;
+        }
+        Some(Ipv6Addr::from(val))
+    }
+}
+
+impl DoubleEndedIterator for Ipv6HostsIter {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let val = self.last;
+        if val == self.next {
+            self.done = true;
+        } else {
+            self.last = val - 1;
+        }
+        Some(Ipv6Addr::from(val))
+    }
+}
+
+impl FusedIterator for Ipv6HostsIter {}
+
+#[derive(Debug, Clone)]
+struct Ipv4SubnetsIter {
+    next: u32,
+    last: u32,
+    step: u32,
+    cidr: u8,
+    done: bool,
+}
+
+impl Iterator for Ipv4SubnetsIter {
+    type Item = Ipv4Range;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let val = self.next;
+        if val == self.last {
+            self.done = true;
+        } else {
+            self.next = val + self.step;
+        }
+        Some(Ipv4Range {
+            ip: val,
+            cidr: self.cidr,
+        })
+    }
+}
+
+impl DoubleEndedIterator for Ipv4SubnetsIter {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let val = self.last;
+        if val == self.next {
+            self.done = true;
+        } else {
+            self.last = val - self.step;
+        }
+        Some(Ipv4Range {
+            ip: val,
+            cidr: self.cidr,
+        })
+    }
+}
+
+impl FusedIterator for Ipv4SubnetsIter {}
+
+#[derive(Debug, Clone)]
+struct Ipv6SubnetsIter {
+    next: u128,
+    last: u128,
+    step: u128,
+    cidr: u8,
+    done: bool,
+}
+
+impl Iterator for Ipv6SubnetsIter {
+    type Item = Ipv6Range;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let val = self.next;
+        if val == self.last {
+            self.done = true;
+        } else {
+            self.next = val + self.step;
+        }
+        Some(Ipv6Range {
+            ip: val,
+            cidr: self.cidr,
+        })
+    }
+}
+
+impl DoubleEndedIterator for Ipv6SubnetsIter {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let val = self.last;
+        if val == self.next {
+            self.done = true;
+        } else {
+            self.last = val - self.step;
+        }
+        Some(Ipv6Range {
+            ip: val,
+            cidr: self.cidr,
+        })
+    }
+}
+
+impl FusedIterator for Ipv6SubnetsIter {}
+
+/// Classification of a range against the IANA special-purpose address
+/// registries, modeled on the `is_loopback`/`is_global`/... predicates on
+/// std's `IpAddr`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpecialUse {
+    Loopback,
+    Private,
+    LinkLocal,
+    Multicast,
+    Documentation,
+    GloballyRoutable,
+}
+
+impl fmt::Display for SpecialUse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SpecialUse::Loopback => "loopback",
+            SpecialUse::Private => "private",
+            SpecialUse::LinkLocal => "link-local",
+            SpecialUse::Multicast => "multicast",
+            SpecialUse::Documentation => "documentation",
+            SpecialUse::GloballyRoutable => "globally-routable",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Ipv4Range {
+    /// Classify the range against the well-known IANA special-purpose
+    /// IPv4 blocks. A range that only partially overlaps one of these
+    /// blocks is not considered a member of it.
+    fn special_use(&self) -> SpecialUse {
+        let loopback: Ipv4Range = "127.0.0.0/8".parse().unwrap();
+        let private: [Ipv4Range; 3] = [
+            "10.0.0.0/8".parse().unwrap(),
+            "172.16.0.0/12".parse().unwrap(),
+            "192.168.0.0/16".parse().unwrap(),
+        ];
+        let link_local: Ipv4Range = "169.254.0.0/16".parse().unwrap();
+        let multicast: Ipv4Range = "224.0.0.0/4".parse().unwrap();
+        let documentation: Ipv4Range = "192.0.2.0/24".parse().unwrap();
+
+        if self.is_subset_of(&loopback) {
+            SpecialUse::Loopback
+        } else if private.iter().any(|p| self.is_subset_of(p)) {
+            SpecialUse::Private
+        } else if self.is_subset_of(&link_local) {
+            SpecialUse::LinkLocal
+        } else if self.is_subset_of(&multicast) {
+            SpecialUse::Multicast
+        } else if self.is_subset_of(&documentation) {
+            SpecialUse::Documentation
+        } else {
+            SpecialUse::GloballyRoutable
+        }
+    }
+}
+
+impl Ipv6Range {
+    /// Classify the range against the well-known IANA special-purpose
+    /// IPv6 blocks. A range that only partially overlaps one of these
+    /// blocks is not considered a member of it.
+    fn special_use(&self) -> SpecialUse {
+        let loopback: Ipv6Range = "::1/128".parse().unwrap();
+        let private: Ipv6Range = "fc00::/7".parse().unwrap();
+        let link_local: Ipv6Range = "fe80::/10".parse().unwrap();
+        let multicast: Ipv6Range = "ff00::/8".parse().unwrap();
+        let documentation: Ipv6Range = "2001:db8::/32".parse().unwrap();
+
+        if self.is_subset_of(&loopback) {
+            SpecialUse::Loopback
+        } else if self.is_subset_of(&private) {
+            SpecialUse::Private
+        } else if self.is_subset_of(&link_local) {
+            SpecialUse::LinkLocal
+        } else if self.is_subset_of(&multicast) {
+            SpecialUse::Multicast
+        } else if self.is_subset_of(&documentation) {
+            SpecialUse::Documentation
+        } else {
+            SpecialUse::GloballyRoutable
+        }
+    }
+}
+
+/// A CIDR range of either address family, for callers that do not want to
+/// branch on `Ipv4Range` vs. `Ipv6Range` themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IpCidr {
+    V4(Ipv4Range),
+    V6(Ipv6Range),
+}
+
+impl fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IpCidr::V4(r) => write!(f, "{}", r),
+            IpCidr::V6(r) => write!(f, "{}", r),
+        }
+    }
+}
+
+impl IpCidr {
+    fn special_use(&self) -> SpecialUse {
+        match self {
+            IpCidr::V4(r) => r.special_use(),
+            IpCidr::V6(r) => r.special_use(),
+        }
+    }
+}
+
+impl FromStr for IpCidr {
+    type Err = RangeParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<Ipv4Range>() {
+            Ok(r) => Ok(IpCidr::V4(r)),
+            Err(_) => s.parse::<Ipv6Range>().map(IpCidr::V6),
+        }
+    }
+}
+
+impl PartialOrd for IpCidr {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IpCidr {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // all v4 ranges sort before all v6 ranges
+        match (self, other) {
+            (IpCidr::V4(a), IpCidr::V4(b)) => a.cmp(b),
+            (IpCidr::V4(_), IpCidr::V6(_)) => Ordering::Less,
+            (IpCidr::V6(_), IpCidr::V4(_)) => Ordering::Greater,
+            (IpCidr::V6(a), IpCidr::V6(b)) => a.cmp(b),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct IpRangeList {
     v4: Vec<Ipv4Range>,
@@ -276,6 +887,38 @@ impl IpRangeList {
         self
     }
 
+    fn add(&mut self, i: IpCidr) -> &mut Self {
+        match i {
+            IpCidr::V4(r) => self.add_v4(r),
+            IpCidr::V6(r) => self.add_v6(r),
+        }
+    }
+
+    fn substract(&mut self, i: IpCidr) -> &mut Self {
+        match i {
+            IpCidr::V4(r) => self.substract_v4(r),
+            IpCidr::V6(r) => self.substract_v6(r),
+        }
+    }
+
+    fn contains(&self, i: IpCidr) -> bool {
+        match i {
+            IpCidr::V4(r) => self.v4.iter().any(|a| a.is_superset_of(&r)),
+            IpCidr::V6(r) => self.v6.iter().any(|a| a.is_superset_of(&r)),
+        }
+    }
+
+    /// Drop every range that is not classified as globally routable,
+    /// e.g. to scrub loopback/private/documentation ranges out of an
+    /// allow/deny list before using it.
+    fn retain_globally_routable(&mut self) -> &mut Self {
+        self.v4
+            .retain(|r| r.special_use() == SpecialUse::GloballyRoutable);
+        self.v6
+            .retain(|r| r.special_use() == SpecialUse::GloballyRoutable);
+        self
+    }
+
     fn add_list(&mut self, other: IpRangeList) -> &mut Self {
         for i in other.v4 {
             self.add_v4(i);
@@ -296,6 +939,76 @@ impl IpRangeList {
         self
     }
 
+    fn _intersect_v4(a: &[Ipv4Range], b: &[Ipv4Range]) -> Vec<Ipv4Range> {
+        let mut result = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() && j < b.len() {
+            let (x, y) = (a[i], b[j]);
+            if x.is_subset_of(&y) {
+                result.push(x);
+                i += 1;
+            } else if y.is_subset_of(&x) {
+                result.push(y);
+                j += 1;
+            } else if x.broadcast() < y.network() {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        result
+    }
+
+    fn _intersect_v6(a: &[Ipv6Range], b: &[Ipv6Range]) -> Vec<Ipv6Range> {
+        let mut result = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() && j < b.len() {
+            let (x, y) = (a[i], b[j]);
+            if x.is_subset_of(&y) {
+                result.push(x);
+                i += 1;
+            } else if y.is_subset_of(&x) {
+                result.push(y);
+                j += 1;
+            } else if x.last_address() < y.network() {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        result
+    }
+
+    /// Keep only the ranges covered by both `self` and `other`.
+    fn intersect_list(&mut self, other: IpRangeList) -> &mut Self {
+        self.v4 = Self::_intersect_v4(&self.v4, &other.v4);
+        self.v6 = Self::_intersect_v6(&self.v6, &other.v6);
+        let mut idx = 0;
+        while idx < self.v4.len() {
+            self.neighbor_merge_v4(idx);
+            idx += 1;
+        }
+        let mut idx = 0;
+        while idx < self.v6.len() {
+            self.neighbor_merge_v6(idx);
+            idx += 1;
+        }
+        self
+    }
+
+    /// Keep only the ranges covered by exactly one of `self` and `other`,
+    /// i.e. `(self ∪ other) − (self ∩ other)`.
+    fn symmetric_difference_list(&mut self, other: IpRangeList) -> &mut Self {
+        let intersection = {
+            let mut both = self.clone();
+            both.intersect_list(other.clone());
+            both
+        };
+        self.add_list(other);
+        self.substract_list(intersection);
+        self
+    }
+
     fn neighbor_merge_v4(&mut self, idx: usize) -> &mut Self {
         if idx > 0 {
             if let Some(r) = self.v4[idx - 1].merge_with(&self.v4[idx]) {
@@ -380,22 +1093,376 @@ impl IpRangeList {
         }
     }
 
+    fn _substract_single_v4(a: Ipv4Range, b: &Ipv4Range, out: &mut Vec<Ipv4Range>) {
+        if b.is_superset_of(&a) {
+            // b fully covers a, so a is removed entirely
+        } else if a.is_superset_of(b) {
+            // a is a strict superset of b: split a in half and keep the half
+            // that does not contain b, then recurse into the half that does
+            let new_cidr = a.cidr + 1;
+            let half_bit = 1u32 << (32 - new_cidr);
+            let lower = Ipv4Range {
+                ip: a.ip,
+                cidr: new_cidr,
+            };
+            let upper = Ipv4Range {
+                ip: a.ip | half_bit,
+                cidr: new_cidr,
+            };
+            if b.is_subset_of(&lower) {
+                out.push(upper);
+                Self::_substract_single_v4(lower, b, out);
+            } else {
+                out.push(lower);
+                Self::_substract_single_v4(upper, b, out);
+            }
+        } else {
+            // a and b are disjoint
+            out.push(a);
+        }
+    }
+
+    fn _substract_single_v6(a: Ipv6Range, b: &Ipv6Range, out: &mut Vec<Ipv6Range>) {
+        if b.is_superset_of(&a) {
+            // b fully covers a, so a is removed entirely
+        } else if a.is_superset_of(b) {
+            // a is a strict superset of b: split a in half and keep the half
+            // that does not contain b, then recurse into the half that does
+            let new_cidr = a.cidr + 1;
+            let half_bit = 1u128 << (128 - new_cidr);
+            let lower = Ipv6Range {
+                ip: a.ip,
+                cidr: new_cidr,
+            };
+            let upper = Ipv6Range {
+                ip: a.ip | half_bit,
+                cidr: new_cidr,
+            };
+            if b.is_subset_of(&lower) {
+                out.push(upper);
+                Self::_substract_single_v6(lower, b, out);
+            } else {
+                out.push(lower);
+                Self::_substract_single_v6(upper, b, out);
+            }
+        } else {
+            // a and b are disjoint
+            out.push(a);
+        }
+    }
+
     fn substract_v4(&mut self, i: Ipv4Range) -> &mut Self {
-        for it in &mut self.v4 {
-            unimplemented!()
+        let mut result: Vec<Ipv4Range> = Vec::new();
+        for a in self.v4.iter() {
+            Self::_substract_single_v4(*a, &i, &mut result);
+        }
+        result.sort();
+        self.v4 = result;
+        let mut idx = 0;
+        while idx < self.v4.len() {
+            self.neighbor_merge_v4(idx);
+            idx += 1;
         }
         self
     }
 
     fn substract_v6(&mut self, i: Ipv6Range) -> &mut Self {
-        for it in &mut self.v6 {
-            unimplemented!()
+        let mut result: Vec<Ipv6Range> = Vec::new();
+        for a in self.v6.iter() {
+            Self::_substract_single_v6(*a, &i, &mut result);
+        }
+        result.sort();
+        self.v6 = result;
+        let mut idx = 0;
+        while idx < self.v6.len() {
+            self.neighbor_merge_v6(idx);
+            idx += 1;
         }
         self
     }
+
+    /// Number of distinct IPv4 addresses covered by the canonicalized list.
+    fn total_addresses_v4(&self) -> u64 {
+        self.v4
+            .iter()
+            .fold(0u64, |acc, r| acc.saturating_add(r.host_count()))
+    }
+
+    /// Number of distinct IPv6 addresses covered by the canonicalized list,
+    /// saturated to `u128::MAX` if the true total does not fit (e.g. the
+    /// list contains a `/0`, whose 2^128 addresses overflow a `u128`).
+    fn total_addresses_v6(&self) -> u128 {
+        self.v6
+            .iter()
+            .fold(0u128, |acc, r| acc.saturating_add(r.host_count()))
+    }
+}
+
+/// Output format for `format_output`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// One CIDR per line, e.g. `10.0.0.0/8`.
+    Cidr,
+    /// One `start-end` address pair per line, e.g. `10.0.0.0-10.255.255.255`.
+    Range,
+    /// Every individual host address, one per line.
+    Hosts,
+    /// One line per range with its netmask, hostmask and `special_use()`
+    /// classification, e.g. `192.168.1.0/24 netmask=255.255.255.0
+    /// hostmask=0.0.0.255 special_use=private`.
+    Details,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cidr" => Ok(OutputFormat::Cidr),
+            "range" => Ok(OutputFormat::Range),
+            "hosts" => Ok(OutputFormat::Hosts),
+            "details" => Ok(OutputFormat::Details),
+            _ => Err(format!(
+                "unknown format '{}', expected cidr|range|hosts|details",
+                s
+            )),
+        }
+    }
 }
 
-fn main() {}
+/// `OutputFormat::Hosts` refuses to enumerate a range wider than this many
+/// addresses, so that e.g. `::/0` produces a note instead of an attempt to
+/// print 2^128 lines.
+const MAX_HOSTS_TO_ENUMERATE: u128 = 1 << 20;
+
+/// Render the canonical list in the given format, one entry per line.
+fn format_output(list: &IpRangeList, format: OutputFormat) -> String {
+    let mut out = String::new();
+    match format {
+        OutputFormat::Cidr => {
+            for r in &list.v4 {
+                out.push_str(&format!("{}\n", r));
+            }
+            for r in &list.v6 {
+                out.push_str(&format!("{}\n", r));
+            }
+        }
+        OutputFormat::Range => {
+            for r in &list.v4 {
+                out.push_str(&format!("{}-{}\n", r.network(), r.broadcast()));
+            }
+            for r in &list.v6 {
+                out.push_str(&format!("{}-{}\n", r.network(), r.last_address()));
+            }
+        }
+        OutputFormat::Hosts => {
+            for r in &list.v4 {
+                if u128::from(r.host_count()) > MAX_HOSTS_TO_ENUMERATE {
+                    out.push_str(&format!(
+                        "# skipping {}: {} addresses exceeds the {}-address host enumeration limit\n",
+                        r,
+                        r.host_count(),
+                        MAX_HOSTS_TO_ENUMERATE
+                    ));
+                    continue;
+                }
+                for a in r.hosts() {
+                    out.push_str(&format!("{}\n", a));
+                }
+            }
+            for r in &list.v6 {
+                if r.host_count() > MAX_HOSTS_TO_ENUMERATE {
+                    out.push_str(&format!(
+                        "# skipping {}: {} addresses exceeds the {}-address host enumeration limit\n",
+                        r,
+                        r.host_count(),
+                        MAX_HOSTS_TO_ENUMERATE
+                    ));
+                    continue;
+                }
+                for a in r.hosts() {
+                    out.push_str(&format!("{}\n", a));
+                }
+            }
+        }
+        OutputFormat::Details => {
+            for r in &list.v4 {
+                out.push_str(&format!(
+                    "{} netmask={} hostmask={} special_use={}\n",
+                    r,
+                    r.netmask(),
+                    r.hostmask(),
+                    r.special_use()
+                ));
+            }
+            for r in &list.v6 {
+                out.push_str(&format!(
+                    "{} netmask={} hostmask={} special_use={}\n",
+                    r,
+                    r.netmask(),
+                    r.hostmask(),
+                    r.special_use()
+                ));
+            }
+        }
+    }
+    out
+}
+
+/// Render every range in `list` split into its `new_prefix` child subnets,
+/// one per line. A range is left unsplit if `new_prefix` does not fit its
+/// address family (e.g. a v6-sized prefix applied to the v4 half of the
+/// list) or is shorter than the range's own prefix.
+fn format_subnets(list: &IpRangeList, new_prefix: u8) -> String {
+    let mut out = String::new();
+    for r in &list.v4 {
+        if new_prefix <= 32 && new_prefix >= r.cidr {
+            for sub in r.subnets(new_prefix) {
+                out.push_str(&format!("{}\n", sub));
+            }
+        } else {
+            out.push_str(&format!("{}\n", r));
+        }
+    }
+    for r in &list.v6 {
+        if new_prefix <= 128 && new_prefix >= r.cidr {
+            for sub in r.subnets(new_prefix) {
+                out.push_str(&format!("{}\n", sub));
+            }
+        } else {
+            out.push_str(&format!("{}\n", r));
+        }
+    }
+    out
+}
+
+/// Apply one line of input to `list`. A line is a CIDR or bare address,
+/// optionally prefixed with `+` (add, the default) or `-` (subtract).
+/// Blank lines and lines starting with `#` are ignored. Parse failures are
+/// reported on stderr and otherwise skipped, so one bad line in a large
+/// input file does not abort the whole run.
+fn apply_command(list: &mut IpRangeList, line: &str) {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return;
+    }
+    let (subtract, rest) = match line.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, line.strip_prefix('+').unwrap_or(line)),
+    };
+    match rest.trim().parse::<IpCidr>() {
+        Ok(cidr) if subtract => {
+            list.substract(cidr);
+        }
+        Ok(cidr) => {
+            list.add(cidr);
+        }
+        Err(_) => {
+            eprintln!("ipcalculator: ignoring invalid CIDR '{}'", rest.trim());
+        }
+    }
+}
+
+/// Command-line options for the `ipcalc`-style front end.
+struct CliArgs {
+    format: OutputFormat,
+    files: Vec<String>,
+    split: Option<u8>,
+    global_only: bool,
+}
+
+/// Parse `--format`/`-f cidr|range|hosts|details`, `--split <prefix>`,
+/// `--global-only` and a list of input files out of the process arguments
+/// (argv[1..]). Remaining arguments are treated as files to read commands
+/// from; if none are given, commands are read from stdin instead.
+fn parse_cli_args(args: &[String]) -> Result<CliArgs, String> {
+    let mut format = OutputFormat::Cidr;
+    let mut files = Vec::new();
+    let mut split = None;
+    let mut global_only = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--format" | "-f" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| "missing value for --format".to_string())?;
+                format = value.parse()?;
+            }
+            "--split" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| "missing value for --split".to_string())?;
+                split = Some(
+                    value
+                        .parse::<u8>()
+                        .map_err(|_| format!("invalid prefix length '{}' for --split", value))?,
+                );
+            }
+            "--global-only" => {
+                global_only = true;
+            }
+            other => files.push(other.to_string()),
+        }
+    }
+    Ok(CliArgs {
+        format,
+        files,
+        split,
+        global_only,
+    })
+}
+
+/// Read every line of input, from the given files in order, or from stdin
+/// if no files are given.
+fn read_commands(files: &[String]) -> io::Result<Vec<String>> {
+    let mut lines = Vec::new();
+    if files.is_empty() {
+        for line in io::stdin().lock().lines() {
+            lines.push(line?);
+        }
+    } else {
+        for path in files {
+            let content = fs::read_to_string(path)?;
+            lines.extend(content.lines().map(String::from));
+        }
+    }
+    Ok(lines)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let cli = match parse_cli_args(&args) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("ipcalculator: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let commands = match read_commands(&cli.files) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("ipcalculator: error reading input: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut list = IpRangeList::new();
+    for line in &commands {
+        apply_command(&mut list, line);
+    }
+
+    if cli.global_only {
+        list.retain_globally_routable();
+    }
+
+    if let Some(new_prefix) = cli.split {
+        print!("{}", format_subnets(&list, new_prefix));
+    } else {
+        print!("{}", format_output(&list, cli.format));
+    }
+    println!("total ipv4 addresses: {}", list.total_addresses_v4());
+    println!("total ipv6 addresses: {}", list.total_addresses_v6());
+}
 
 #[cfg(test)]
 mod tests {
@@ -540,6 +1607,396 @@ mod tests {
         );
     }
 
+    #[test]
+    fn substract_v4_host_from_24() {
+        let mut l = IpRangeList::new();
+        l.add_v4("192.168.1.0/24".parse().unwrap());
+        l.substract_v4("192.168.1.17/32".parse().unwrap());
+        let total: u64 = l.v4.iter().map(|r| 1u64 << (32 - r.cidr)).sum();
+        assert!(total == 255);
+        for r in &l.v4 {
+            assert!(!r.is_superset_of(&"192.168.1.17/32".parse().unwrap()));
+        }
+    }
+
+    #[test]
+    fn substract_v4_24_from_16() {
+        let mut l = IpRangeList::new();
+        l.add_v4("192.168.0.0/16".parse().unwrap());
+        l.substract_v4("192.168.5.0/24".parse().unwrap());
+        let total: u64 = l.v4.iter().map(|r| 1u64 << (32 - r.cidr)).sum();
+        assert!(total == (1u64 << 16) - 256);
+        for r in &l.v4 {
+            assert!(!r.is_superset_of(&"192.168.5.0/24".parse().unwrap()));
+        }
+    }
+
+    #[test]
+    fn substract_v4_disjoint_is_noop() {
+        let mut l = IpRangeList::new();
+        l.add_v4("192.168.0.0/24".parse().unwrap());
+        l.substract_v4("10.0.0.0/8".parse().unwrap());
+        assert!(l.v4.len() == 1);
+        assert!(l.v4[0] == "192.168.0.0/24".parse().unwrap());
+    }
+
+    #[test]
+    fn ip_cidr_parse_and_order() {
+        let a: IpCidr = "192.168.0.0/24".parse().unwrap();
+        let b: IpCidr = "::1/128".parse().unwrap();
+        assert!(matches!(a, IpCidr::V4(_)));
+        assert!(matches!(b, IpCidr::V6(_)));
+        assert!(a < b);
+        assert!("not an ip".parse::<IpCidr>().is_err());
+    }
+
+    #[test]
+    fn bare_v6_address_is_a_single_host() {
+        let r: Ipv6Range = "2001:db8::1".parse().unwrap();
+        assert!(r.cidr == 128);
+        assert!(r == "2001:db8::1/128".parse().unwrap());
+
+        let c: IpCidr = "2001:db8::1".parse().unwrap();
+        assert!(c == IpCidr::V6("2001:db8::1/128".parse().unwrap()));
+    }
+
+    #[test]
+    fn ip_cidr_dispatch_on_list() {
+        let mut l = IpRangeList::new();
+        l.add("192.168.0.0/24".parse().unwrap());
+        l.add("fd00::/8".parse().unwrap());
+        assert!(l.contains("192.168.0.5/32".parse().unwrap()));
+        assert!(l.contains("fd00::1/128".parse().unwrap()));
+        assert!(!l.contains("10.0.0.0/8".parse().unwrap()));
+        l.substract("192.168.0.5/32".parse().unwrap());
+        assert!(!l.contains("192.168.0.5/32".parse().unwrap()));
+    }
+
+    #[test]
+    fn hosts_v4() {
+        let r: Ipv4Range = "192.168.1.0/30".parse().unwrap();
+        let v: Vec<Ipv4Addr> = r.hosts().collect();
+        assert!(
+            v == vec![
+                "192.168.1.0".parse::<Ipv4Addr>().unwrap(),
+                "192.168.1.1".parse().unwrap(),
+                "192.168.1.2".parse().unwrap(),
+                "192.168.1.3".parse().unwrap(),
+            ]
+        );
+        assert!(r.hosts().rev().next().unwrap() == "192.168.1.3".parse::<Ipv4Addr>().unwrap());
+    }
+
+    #[test]
+    fn hosts_v4_single_host() {
+        let r: Ipv4Range = "1.2.3.4/32".parse().unwrap();
+        let v: Vec<Ipv4Addr> = r.hosts().collect();
+        assert!(v == vec!["1.2.3.4".parse::<Ipv4Addr>().unwrap()]);
+    }
+
+    #[test]
+    fn hosts_v6_zero_does_not_overflow() {
+        let r: Ipv6Range = "::/0".parse().unwrap();
+        let mut it = r.hosts();
+        assert!(it.next().unwrap() == "::".parse::<Ipv6Addr>().unwrap());
+        assert!(
+            it.next_back().unwrap()
+                == "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff"
+                    .parse::<Ipv6Addr>()
+                    .unwrap()
+        );
+    }
+
+    #[test]
+    fn subnets_v4() {
+        let r: Ipv4Range = "192.168.0.0/24".parse().unwrap();
+        let v: Vec<Ipv4Range> = r.subnets(26).collect();
+        assert!(
+            v == vec![
+                "192.168.0.0/26".parse().unwrap(),
+                "192.168.0.64/26".parse().unwrap(),
+                "192.168.0.128/26".parse().unwrap(),
+                "192.168.0.192/26".parse().unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn subnets_v4_too_short_prefix_is_empty() {
+        let r: Ipv4Range = "192.168.0.0/24".parse().unwrap();
+        assert!(r.subnets(23).next().is_none());
+    }
+
+    #[test]
+    fn saturating_add_sub_v4() {
+        let max: Ipv4Addr = "255.255.255.255".parse().unwrap();
+        let min: Ipv4Addr = "0.0.0.0".parse().unwrap();
+        assert!(max.saturating_add(5) == max);
+        assert!(min.saturating_sub(1) == min);
+    }
+
+    #[test]
+    fn saturating_add_sub_v6() {
+        let addr: Ipv6Addr = "fd00::".parse().unwrap();
+        assert!(
+            addr.saturating_sub(1)
+                == "fcff:ffff:ffff:ffff:ffff:ffff:ffff:ffff"
+                    .parse::<Ipv6Addr>()
+                    .unwrap()
+        );
+        let min: Ipv6Addr = "::".parse().unwrap();
+        assert!(min.saturating_sub(1) == min);
+        let max: Ipv6Addr = "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff".parse().unwrap();
+        assert!(max.saturating_add(1) == max);
+    }
+
+    #[test]
+    fn network_broadcast_netmask_hostmask_v4() {
+        let r: Ipv4Range = "192.168.1.5/24".parse().unwrap();
+        assert!(r.network() == "192.168.1.0".parse::<Ipv4Addr>().unwrap());
+        assert!(r.broadcast() == "192.168.1.255".parse::<Ipv4Addr>().unwrap());
+        assert!(r.netmask() == "255.255.255.0".parse::<Ipv4Addr>().unwrap());
+        assert!(r.hostmask() == "0.0.0.255".parse::<Ipv4Addr>().unwrap());
+    }
+
+    #[test]
+    fn network_last_address_netmask_hostmask_v6() {
+        let r: Ipv6Range = "fd00::/8".parse().unwrap();
+        assert!(r.network() == "fd00::".parse::<Ipv6Addr>().unwrap());
+        assert!(
+            r.last_address()
+                == "fdff:ffff:ffff:ffff:ffff:ffff:ffff:ffff"
+                    .parse::<Ipv6Addr>()
+                    .unwrap()
+        );
+        assert!(r.netmask() == "ff00::".parse::<Ipv6Addr>().unwrap());
+        assert!(
+            r.hostmask()
+                == "ff:ffff:ffff:ffff:ffff:ffff:ffff:ffff"
+                    .parse::<Ipv6Addr>()
+                    .unwrap()
+        );
+    }
+
+    #[test]
+    fn special_use_v4() {
+        let loopback: Ipv4Range = "127.0.0.1/32".parse().unwrap();
+        let private: Ipv4Range = "192.168.1.0/24".parse().unwrap();
+        let link_local: Ipv4Range = "169.254.1.1/32".parse().unwrap();
+        let multicast: Ipv4Range = "224.0.0.1/32".parse().unwrap();
+        let documentation: Ipv4Range = "192.0.2.0/24".parse().unwrap();
+        let global: Ipv4Range = "8.8.8.0/24".parse().unwrap();
+
+        assert!(loopback.special_use() == SpecialUse::Loopback);
+        assert!(private.special_use() == SpecialUse::Private);
+        assert!(link_local.special_use() == SpecialUse::LinkLocal);
+        assert!(multicast.special_use() == SpecialUse::Multicast);
+        assert!(documentation.special_use() == SpecialUse::Documentation);
+        assert!(global.special_use() == SpecialUse::GloballyRoutable);
+    }
+
+    #[test]
+    fn special_use_v6() {
+        let loopback: Ipv6Range = "::1/128".parse().unwrap();
+        let private: Ipv6Range = "fd00::/8".parse().unwrap();
+        let link_local: Ipv6Range = "fe80::1/128".parse().unwrap();
+        let documentation: Ipv6Range = "2001:db8::/32".parse().unwrap();
+        let global: Ipv6Range = "2001:4860:4860::/48".parse().unwrap();
+
+        assert!(loopback.special_use() == SpecialUse::Loopback);
+        assert!(private.special_use() == SpecialUse::Private);
+        assert!(link_local.special_use() == SpecialUse::LinkLocal);
+        assert!(documentation.special_use() == SpecialUse::Documentation);
+        assert!(global.special_use() == SpecialUse::GloballyRoutable);
+    }
+
+    #[test]
+    fn retain_globally_routable() {
+        let mut l = IpRangeList::new();
+        l.add("8.8.8.0/24".parse().unwrap());
+        l.add("192.168.0.0/16".parse().unwrap());
+        l.add("2001:4860:4860::/48".parse().unwrap());
+        l.add("fd00::/8".parse().unwrap());
+        l.retain_globally_routable();
+        assert!(l.v4.len() == 1);
+        assert!(l.v6.len() == 1);
+        assert!(l.v4[0] == "8.8.8.0/24".parse().unwrap());
+        assert!(l.v6[0] == "2001:4860:4860::/48".parse().unwrap());
+    }
+
+    #[test]
+    fn apply_command_add_and_substract() {
+        let mut l = IpRangeList::new();
+        apply_command(&mut l, "+192.168.0.0/24");
+        apply_command(&mut l, "192.168.1.0/24"); // no prefix defaults to add
+        apply_command(&mut l, "-192.168.0.128/25");
+        apply_command(&mut l, "# a comment");
+        apply_command(&mut l, "");
+        apply_command(&mut l, "not a cidr");
+        assert!(l.total_addresses_v4() == 384);
+    }
+
+    #[test]
+    fn apply_command_bare_v6_is_a_single_host() {
+        let mut l = IpRangeList::new();
+        apply_command(&mut l, "+2001:db8::1");
+        assert!(l.total_addresses_v6() == 1);
+        assert!(l.contains("2001:db8::1/128".parse().unwrap()));
+
+        apply_command(&mut l, "-2001:db8::1");
+        assert!(l.total_addresses_v6() == 0);
+    }
+
+    #[test]
+    fn format_output_variants() {
+        let mut l = IpRangeList::new();
+        l.add("192.168.0.0/30".parse().unwrap());
+        assert!(format_output(&l, OutputFormat::Cidr) == "192.168.0.0/30\n");
+        assert!(format_output(&l, OutputFormat::Range) == "192.168.0.0-192.168.0.3\n");
+        assert!(
+            format_output(&l, OutputFormat::Hosts)
+                == "192.168.0.0\n192.168.0.1\n192.168.0.2\n192.168.0.3\n"
+        );
+    }
+
+    #[test]
+    fn format_output_details_includes_netmask_hostmask_and_special_use() {
+        let mut l = IpRangeList::new();
+        l.add("192.168.0.0/24".parse().unwrap());
+        let out = format_output(&l, OutputFormat::Details);
+        assert!(out.contains("netmask=255.255.255.0"));
+        assert!(out.contains("hostmask=0.0.0.255"));
+        assert!(out.contains("special_use=private"));
+    }
+
+    #[test]
+    fn total_addresses_v6_zero_does_not_overflow() {
+        let mut l = IpRangeList::new();
+        l.add_v6("::/0".parse().unwrap());
+        assert!(l.total_addresses_v6() == u128::max_value());
+    }
+
+    #[test]
+    fn format_output_hosts_skips_oversized_ranges() {
+        let mut l = IpRangeList::new();
+        l.add_v6("::/0".parse().unwrap());
+        let out = format_output(&l, OutputFormat::Hosts);
+        assert!(out.starts_with("# skipping"));
+
+        let mut l = IpRangeList::new();
+        l.add_v4("0.0.0.0/0".parse().unwrap());
+        let out = format_output(&l, OutputFormat::Hosts);
+        assert!(out.starts_with("# skipping"));
+    }
+
+    #[test]
+    fn parse_cli_args_defaults_and_format() {
+        let cli = parse_cli_args(&[]).unwrap();
+        assert!(cli.format == OutputFormat::Cidr);
+        assert!(cli.files.is_empty());
+
+        let args: Vec<String> = vec!["--format".into(), "range".into(), "input.txt".into()];
+        let cli = parse_cli_args(&args).unwrap();
+        assert!(cli.format == OutputFormat::Range);
+        assert!(cli.files == vec!["input.txt".to_string()]);
+
+        assert!(parse_cli_args(&["--format".into(), "bogus".into()]).is_err());
+    }
+
+    #[test]
+    fn parse_cli_args_split() {
+        let args: Vec<String> = vec!["--split".into(), "28".into()];
+        let cli = parse_cli_args(&args).unwrap();
+        assert!(cli.split == Some(28));
+        assert!(cli.files.is_empty());
+
+        assert!(parse_cli_args(&["--split".into(), "not-a-number".into()]).is_err());
+    }
+
+    #[test]
+    fn parse_cli_args_global_only() {
+        let cli = parse_cli_args(&[]).unwrap();
+        assert!(!cli.global_only);
+
+        let cli = parse_cli_args(&["--global-only".into()]).unwrap();
+        assert!(cli.global_only);
+    }
+
+    #[test]
+    fn format_subnets_splits_into_child_prefixes() {
+        let mut l = IpRangeList::new();
+        l.add_v4("192.168.0.0/23".parse().unwrap());
+        let out = format_subnets(&l, 24);
+        assert!(out == "192.168.0.0/24\n192.168.1.0/24\n");
+
+        let mut l = IpRangeList::new();
+        l.add_v6("2001:db8::/32".parse().unwrap());
+        let out = format_subnets(&l, 33);
+        assert!(out == "2001:db8::/33\n2001:db8:8000::/33\n");
+    }
+
+    #[test]
+    fn format_subnets_leaves_range_unsplit_when_prefix_does_not_fit() {
+        let mut l = IpRangeList::new();
+        l.add_v4("192.168.0.0/24".parse().unwrap());
+        let out = format_subnets(&l, 200);
+        assert!(out == "192.168.0.0/24\n");
+    }
+
+    #[test]
+    fn intersect_list_overlapping_24s_in_common_16() {
+        let mut a = IpRangeList::new();
+        a.add_v4("192.168.0.0/23".parse().unwrap()); // covers .0.0/24 and .1.0/24
+        let mut b = IpRangeList::new();
+        b.add_v4("192.168.1.0/24".parse().unwrap());
+        b.add_v4("192.168.5.0/24".parse().unwrap()); // disjoint from a
+
+        a.intersect_list(b);
+        assert!(a.v4.len() == 1);
+        assert!(a.v4[0] == "192.168.1.0/24".parse().unwrap());
+    }
+
+    #[test]
+    fn intersect_list_disjoint_is_empty() {
+        let mut a = IpRangeList::new();
+        a.add_v4("10.0.0.0/8".parse().unwrap());
+        let mut b = IpRangeList::new();
+        b.add_v4("192.168.0.0/16".parse().unwrap());
+
+        a.intersect_list(b);
+        assert!(a.v4.is_empty());
+    }
+
+    #[test]
+    fn symmetric_difference_list_overlapping() {
+        let mut a = IpRangeList::new();
+        a.add_v4("192.168.0.0/23".parse().unwrap());
+        let mut b = IpRangeList::new();
+        b.add_v4("192.168.1.0/24".parse().unwrap());
+        b.add_v4("192.168.5.0/24".parse().unwrap());
+
+        a.symmetric_difference_list(b);
+        // .1.0/24 is shared and cancels out; .0.0/24 (only in a) and
+        // .5.0/24 (only in b) remain
+        let total: u64 = a.v4.iter().map(|r| 1u64 << (32 - r.cidr)).sum();
+        assert!(total == 512);
+        assert!(!a.contains("192.168.1.0/24".parse().unwrap()));
+        assert!(a.contains("192.168.0.0/24".parse().unwrap()));
+        assert!(a.contains("192.168.5.0/24".parse().unwrap()));
+    }
+
+    #[test]
+    fn symmetric_difference_list_disjoint_is_union() {
+        let mut a = IpRangeList::new();
+        a.add_v4("10.0.0.0/8".parse().unwrap());
+        let mut b = IpRangeList::new();
+        b.add_v4("192.168.0.0/16".parse().unwrap());
+
+        a.symmetric_difference_list(b);
+        assert!(a.contains("10.0.0.0/8".parse().unwrap()));
+        assert!(a.contains("192.168.0.0/16".parse().unwrap()));
+    }
+
     fn _generate_random_list() -> IpRangeList {
         match rand::thread_rng().gen_range(1..=2) {
             1..=2 => (),